@@ -0,0 +1,47 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+/// Computes the SHA-256 digest of `path`, reading it in fixed-size chunks so
+/// large tarballs don't have to be loaded into memory whole.
+pub fn sha256_hex(path: &Path) -> Result<String, Box<dyn Error>> {
+    let file = File::open(path)?;
+    let mut reader = BufReader::new(file);
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+
+    loop {
+        let bytes_read = reader.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_sha256_hex_matches_known_digest() {
+        // NIST test vector for the ASCII string "abc".
+        let path = std::env::temp_dir().join(format!("simplex-checksum-test-{}.txt", std::process::id()));
+        fs::write(&path, b"abc").unwrap();
+
+        let digest = sha256_hex(&path).unwrap();
+        assert_eq!(
+            digest,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+}
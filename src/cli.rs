@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use toml::Value;
+
+/// Subcommands `simplex` understands, used for "did you mean" suggestions.
+pub const KNOWN_COMMANDS: &[&str] = &["install", "remove", "list", "info", "purge", "lock"];
+
+/// Classic edit-distance DP over a single row buffer: `curr_row[j]` is the min
+/// of delete/insert/substitute, with substitution cost 0 when characters match.
+fn levenshtein_distance(a: &str, b: &str) -> usize {
+    let a_chars: Vec<char> = a.chars().collect();
+    let b_chars: Vec<char> = b.chars().collect();
+
+    let mut prev_row: Vec<usize> = (0..=b_chars.len()).collect();
+    for i in 1..=a_chars.len() {
+        let mut curr_row = vec![i; b_chars.len() + 1];
+        for j in 1..=b_chars.len() {
+            let substitution_cost = if a_chars[i - 1] == b_chars[j - 1] { 0 } else { 1 };
+            curr_row[j] = (prev_row[j] + 1)
+                .min(curr_row[j - 1] + 1)
+                .min(prev_row[j - 1] + substitution_cost);
+        }
+        prev_row = curr_row;
+    }
+
+    prev_row[b_chars.len()]
+}
+
+/// Finds the known command closest to `input`, if it's within roughly a third
+/// of that command's length (so `instal` suggests `install` but `list`
+/// doesn't suggest `info`).
+pub fn suggest_command(input: &str, known_commands: &[&str]) -> Option<String> {
+    known_commands
+        .iter()
+        .map(|&command| (command, levenshtein_distance(input, command)))
+        .min_by_key(|&(_, distance)| distance)
+        .filter(|&(command, distance)| distance <= command.len().div_ceil(3).max(1))
+        .map(|(command, _)| command.to_string())
+}
+
+fn parse_aliases(toml: &Value) -> Result<HashMap<String, Vec<String>>, Box<dyn Error>> {
+    let binding = toml::value::Table::new();
+    let alias_table = toml
+        .get("alias")
+        .map(|v| v.as_table())
+        .unwrap_or(None)
+        .unwrap_or(&binding);
+
+    alias_table
+        .iter()
+        .map(|(name, value)| {
+            let expansion = match value {
+                Value::String(command_line) => {
+                    command_line.split_whitespace().map(String::from).collect()
+                }
+                Value::Array(words) => words
+                    .iter()
+                    .map(|word| word.as_str().ok_or("Invalid alias entry").map(String::from))
+                    .collect::<Result<Vec<_>, _>>()?,
+                _ => return Err(format!("Invalid alias definition for '{}'", name).into()),
+            };
+            Ok((name.clone(), expansion))
+        })
+        .collect()
+}
+
+/// Reads the `[alias]` table from `config_path` (e.g. `store/config.toml`), if
+/// it exists. A missing config file just means no user-defined aliases.
+pub fn load_aliases(config_path: &Path) -> Result<HashMap<String, Vec<String>>, Box<dyn Error>> {
+    if !config_path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let toml_str = fs::read_to_string(config_path)?;
+    let toml: Value = toml::from_str(&toml_str)?;
+    parse_aliases(&toml)
+}
+
+/// Expands `args[1]` into its alias command sequence, if it names one, leaving
+/// the binary name and any trailing arguments untouched.
+pub fn expand_aliases(args: Vec<String>, aliases: &HashMap<String, Vec<String>>) -> Vec<String> {
+    if args.len() < 2 {
+        return args;
+    }
+
+    match aliases.get(&args[1]) {
+        Some(expansion) => {
+            let mut expanded = Vec::with_capacity(args.len() - 2 + expansion.len() + 1);
+            expanded.push(args[0].clone());
+            expanded.extend(expansion.iter().cloned());
+            expanded.extend(args[2..].iter().cloned());
+            expanded
+        }
+        None => args,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levenshtein_distance() {
+        assert_eq!(levenshtein_distance("install", "install"), 0);
+        assert_eq!(levenshtein_distance("instal", "install"), 1);
+        assert_eq!(levenshtein_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_suggest_command_close_typo() {
+        assert_eq!(
+            suggest_command("instal", KNOWN_COMMANDS),
+            Some("install".to_string())
+        );
+    }
+
+    #[test]
+    fn test_suggest_command_no_close_match() {
+        assert_eq!(suggest_command("xyz", KNOWN_COMMANDS), None);
+    }
+
+    #[test]
+    fn test_expand_aliases() {
+        let mut aliases = HashMap::new();
+        aliases.insert("rm".to_string(), vec!["remove".to_string()]);
+
+        let args = vec![
+            "simplex".to_string(),
+            "rm".to_string(),
+            "sqlite".to_string(),
+            "3.36.0".to_string(),
+        ];
+        assert_eq!(
+            expand_aliases(args, &aliases),
+            vec!["simplex", "remove", "sqlite", "3.36.0"]
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+use rusqlite::{params, Connection};
+
+use crate::Package;
+
+/// Persistent store for installed package metadata, backed by a SQLite
+/// database under `store_path` (see `PackageManager::new`). This is what
+/// lets `sync_installed_packages` recover full `Package` records (deps,
+/// build steps, url) across restarts instead of reconstructing them from
+/// directory names alone.
+pub struct InstallDb {
+    conn: Connection,
+}
+
+impl InstallDb {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let conn = Connection::open(path)?;
+        Self::from_connection(conn)
+    }
+
+    /// In-memory variant used by tests so they don't need a temp file on disk.
+    #[cfg(test)]
+    fn open_in_memory() -> Result<Self, Box<dyn Error>> {
+        let conn = Connection::open_in_memory()?;
+        Self::from_connection(conn)
+    }
+
+    fn from_connection(conn: Connection) -> Result<Self, Box<dyn Error>> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS packages (
+                id          TEXT PRIMARY KEY,
+                name        TEXT NOT NULL,
+                version     TEXT NOT NULL,
+                url         TEXT NOT NULL,
+                sha256      TEXT NOT NULL,
+                build_steps TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS package_deps (
+                package_id  TEXT NOT NULL REFERENCES packages(id),
+                dep_name    TEXT NOT NULL,
+                dep_version TEXT NOT NULL
+            );",
+        )?;
+        Ok(InstallDb { conn })
+    }
+
+    /// Writes `package` and its dependency edges, replacing any existing
+    /// row for `name-version`.
+    pub fn insert_package(&self, package: &Package) -> Result<(), Box<dyn Error>> {
+        let id = format!("{}-{}", package.name, package.version);
+        self.conn.execute(
+            "INSERT INTO packages (id, name, version, url, sha256, build_steps)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(id) DO UPDATE SET
+                name = excluded.name,
+                version = excluded.version,
+                url = excluded.url,
+                sha256 = excluded.sha256,
+                build_steps = excluded.build_steps",
+            params![
+                id,
+                package.name,
+                package.version,
+                package.url,
+                package.sha256,
+                package.build_steps.join("\n"),
+            ],
+        )?;
+
+        self.conn
+            .execute("DELETE FROM package_deps WHERE package_id = ?1", params![id])?;
+        for (dep_name, dep_version) in &package.dependencies {
+            self.conn.execute(
+                "INSERT INTO package_deps (package_id, dep_name, dep_version) VALUES (?1, ?2, ?3)",
+                params![id, dep_name, dep_version],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn remove_package(&self, id: &str) -> Result<(), Box<dyn Error>> {
+        self.conn
+            .execute("DELETE FROM package_deps WHERE package_id = ?1", params![id])?;
+        self.conn
+            .execute("DELETE FROM packages WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    pub fn get_package(&self, id: &str) -> Result<Option<Package>, Box<dyn Error>> {
+        let package = self
+            .conn
+            .query_row(
+                "SELECT name, version, url, sha256, build_steps FROM packages WHERE id = ?1",
+                params![id],
+                |row| {
+                    let name: String = row.get(0)?;
+                    let version: String = row.get(1)?;
+                    let url: String = row.get(2)?;
+                    let sha256: String = row.get(3)?;
+                    let build_steps: String = row.get(4)?;
+                    Ok((name, version, url, sha256, build_steps))
+                },
+            )
+            .map(Some)
+            .or_else(|err| match err {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                err => Err(err),
+            })?;
+
+        let Some((name, version, url, sha256, build_steps)) = package else {
+            return Ok(None);
+        };
+
+        Ok(Some(Package {
+            name,
+            version,
+            url,
+            sha256,
+            build_steps: build_steps.split('\n').filter(|s| !s.is_empty()).map(String::from).collect(),
+            dependencies: self.get_dependencies(id)?,
+        }))
+    }
+
+    pub fn list_packages(&self) -> Result<HashMap<String, Package>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare("SELECT id FROM packages")?;
+        let ids = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut packages = HashMap::new();
+        for id in ids {
+            if let Some(package) = self.get_package(&id)? {
+                packages.insert(id, package);
+            }
+        }
+        Ok(packages)
+    }
+
+    /// Returns the ids (`name-version`) of installed packages that depend on
+    /// exactly `dep_name-dep_version`, used to guard removal of a still-needed
+    /// package. `package_deps` stores the resolved version a dependency was
+    /// actually installed at (not the TOML range), so this only matches the
+    /// specific version being removed - installing `libfoo-2.0` as a dependency
+    /// doesn't block removing a standalone `libfoo-1.0`.
+    pub fn find_dependents(&self, dep_name: &str, dep_version: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT package_id FROM package_deps WHERE dep_name = ?1 AND dep_version = ?2",
+        )?;
+        let ids = stmt
+            .query_map(params![dep_name, dep_version], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ids)
+    }
+
+    fn get_dependencies(&self, package_id: &str) -> Result<HashMap<String, String>, Box<dyn Error>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT dep_name, dep_version FROM package_deps WHERE package_id = ?1")?;
+        let deps = stmt
+            .query_map(params![package_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        Ok(deps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_package() -> Package {
+        let mut dependencies = HashMap::new();
+        dependencies.insert("libsomething".to_string(), "^6.0".to_string());
+
+        Package {
+            name: "sqlite".to_string(),
+            version: "3.36.0".to_string(),
+            dependencies,
+            build_steps: vec!["./configure".to_string(), "make".to_string(), "make install".to_string()],
+            url: "https://www.sqlite.org/2021/sqlite-autoconf-3360000.tar.gz".to_string(),
+            sha256: "bd90c3eb96bee996206b83be7065c9ce19aef38c3f4fb53073ada0d0b69bbce3".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_insert_get_remove_round_trip() {
+        let db = InstallDb::open_in_memory().unwrap();
+        let package = sample_package();
+        let id = format!("{}-{}", package.name, package.version);
+
+        assert!(db.get_package(&id).unwrap().is_none());
+
+        db.insert_package(&package).unwrap();
+        let fetched = db.get_package(&id).unwrap().unwrap();
+        assert_eq!(fetched.name, package.name);
+        assert_eq!(fetched.version, package.version);
+        assert_eq!(fetched.url, package.url);
+        assert_eq!(fetched.sha256, package.sha256);
+        assert_eq!(fetched.build_steps, package.build_steps);
+        assert_eq!(fetched.dependencies, package.dependencies);
+
+        assert_eq!(db.list_packages().unwrap().len(), 1);
+
+        db.remove_package(&id).unwrap();
+        assert!(db.get_package(&id).unwrap().is_none());
+        assert!(db.list_packages().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_insert_overwrites_existing_row() {
+        let db = InstallDb::open_in_memory().unwrap();
+        let mut package = sample_package();
+        db.insert_package(&package).unwrap();
+
+        package.url = "https://example.com/new-location.tar.gz".to_string();
+        db.insert_package(&package).unwrap();
+
+        let id = format!("{}-{}", package.name, package.version);
+        let fetched = db.get_package(&id).unwrap().unwrap();
+        assert_eq!(fetched.url, "https://example.com/new-location.tar.gz");
+        assert_eq!(db.list_packages().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_find_dependents() {
+        let db = InstallDb::open_in_memory().unwrap();
+        let package = sample_package();
+        db.insert_package(&package).unwrap();
+
+        assert_eq!(
+            db.find_dependents("libsomething", "^6.0").unwrap(),
+            vec!["sqlite-3.36.0".to_string()]
+        );
+        assert!(db.find_dependents("libsomething", "^7.0").unwrap().is_empty());
+        assert!(db.find_dependents("nobody-depends-on-this", "^6.0").unwrap().is_empty());
+
+        let id = format!("{}-{}", package.name, package.version);
+        db.remove_package(&id).unwrap();
+        assert!(db.find_dependents("libsomething", "^6.0").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,142 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use toml::value::{Array, Table};
+use toml::Value;
+
+use crate::Package;
+
+/// A single resolved, pinned entry in `simplex.lock`: the exact version, source
+/// url and verified sha256 a range like `^6.0` resolved to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+    pub url: String,
+    pub sha256: String,
+}
+
+impl From<&Package> for LockedPackage {
+    fn from(package: &Package) -> Self {
+        LockedPackage {
+            name: package.name.clone(),
+            version: package.version.clone(),
+            url: package.url.clone(),
+            sha256: package.sha256.clone(),
+        }
+    }
+}
+
+/// `simplex.lock` lives next to the root package description, mirroring where
+/// `Cargo.lock` sits next to `Cargo.toml`.
+pub fn lockfile_path(packages_dir: &Path) -> PathBuf {
+    packages_dir.join("simplex.lock")
+}
+
+fn parse_lockfile(toml_str: &str) -> Result<Vec<LockedPackage>, Box<dyn Error>> {
+    let value: Value = toml::from_str(toml_str)?;
+    let packages = value
+        .get("package")
+        .ok_or("Missing [[package]] entries in lockfile")?
+        .as_array()
+        .ok_or("Invalid [[package]] entries in lockfile")?;
+
+    packages
+        .iter()
+        .map(|entry| {
+            let table = entry.as_table().ok_or("Invalid package entry in lockfile")?;
+            let field = |key: &str| -> Result<String, Box<dyn Error>> {
+                Ok(table
+                    .get(key)
+                    .ok_or_else(|| format!("Missing {} in locked package", key))?
+                    .as_str()
+                    .ok_or_else(|| format!("Invalid {} in locked package", key))?
+                    .to_string())
+            };
+            Ok(LockedPackage {
+                name: field("name")?,
+                version: field("version")?,
+                url: field("url")?,
+                sha256: field("sha256")?,
+            })
+        })
+        .collect()
+}
+
+/// Reads `path`'s locked package closure, or `None` if no lockfile exists yet.
+pub fn read_lockfile(path: &Path) -> Result<Option<Vec<LockedPackage>>, Box<dyn Error>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let toml_str = fs::read_to_string(path)?;
+    Ok(Some(parse_lockfile(&toml_str)?))
+}
+
+/// Writes the fully resolved dependency closure to `path` as `[[package]]` entries.
+pub fn write_lockfile(path: &Path, packages: &[LockedPackage]) -> Result<(), Box<dyn Error>> {
+    let entries: Array = packages
+        .iter()
+        .map(|package| {
+            let mut table = Table::new();
+            table.insert("name".to_string(), Value::String(package.name.clone()));
+            table.insert("version".to_string(), Value::String(package.version.clone()));
+            table.insert("url".to_string(), Value::String(package.url.clone()));
+            table.insert("sha256".to_string(), Value::String(package.sha256.clone()));
+            Value::Table(table)
+        })
+        .collect();
+
+    let mut root = Table::new();
+    root.insert("package".to_string(), Value::Array(entries));
+
+    let toml_str = toml::to_string_pretty(&Value::Table(root))?;
+    fs::write(path, toml_str)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_packages() -> Vec<LockedPackage> {
+        vec![
+            LockedPackage {
+                name: "sqlite".to_string(),
+                version: "3.36.0".to_string(),
+                url: "https://www.sqlite.org/2021/sqlite-autoconf-3360000.tar.gz".to_string(),
+                sha256: "bd90c3eb96bee996206b83be7065c9ce19aef38c3f4fb53073ada0d0b69bbce3".to_string(),
+            },
+            LockedPackage {
+                name: "libsomething".to_string(),
+                version: "6.0.1".to_string(),
+                url: "https://example.com/libsomething-6.0.1.tar.gz".to_string(),
+                sha256: "0".repeat(64),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_write_then_read_lockfile_round_trip() {
+        let path = std::env::temp_dir().join(format!(
+            "simplex-lock-round-trip-{}.lock",
+            std::process::id()
+        ));
+        let packages = sample_packages();
+
+        write_lockfile(&path, &packages).unwrap();
+        let read_back = read_lockfile(&path).unwrap().unwrap();
+        assert_eq!(read_back, packages);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_lockfile_missing_file_returns_none() {
+        let path = std::env::temp_dir().join(format!(
+            "simplex-lock-missing-{}.lock",
+            std::process::id()
+        ));
+        assert!(read_lockfile(&path).unwrap().is_none());
+    }
+}
@@ -3,19 +3,32 @@ use std::env;
 use std::error::Error;
 use std::fmt;
 use std::fs;
+use std::fs::File;
+use std::io;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
+use flate2::read::GzDecoder;
+use tar::Archive;
 use toml;
 use toml::Value;
 
+mod checksum;
+mod cli;
+mod db;
+mod lock;
+mod resolver;
+
+use db::InstallDb;
+
 #[derive(Debug, Clone)]
 struct Package {
     name: String,
     version: String,
-    dependencies: Vec<String>,
+    dependencies: HashMap<String, String>,
     build_steps: Vec<String>,
     url: String,
+    sha256: String,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -151,9 +164,85 @@ fn parse_package_description(toml_str: &str) -> Result<PackageDescription, Box<d
     })
 }
 
+/// Turns a declarative `[build]` section into the shell commands `build_package` runs.
+/// `autoconf` expands to the classic configure/make/make-install chain (with
+/// `Build.arguments` passed to `./configure`); anything else is run as a single
+/// `{system} {arguments...}` command.
+fn build_steps_from_build(build: &Build) -> Vec<String> {
+    match build.system.as_str() {
+        "autoconf" => vec![
+            format!("./configure {}", build.arguments.join(" "))
+                .trim()
+                .to_string(),
+            "make".to_string(),
+            "make install".to_string(),
+        ],
+        _ => vec![format!("{} {}", build.system, build.arguments.join(" "))
+            .trim()
+            .to_string()],
+    }
+}
+
+fn package_from_description(description: &PackageDescription) -> Package {
+    Package {
+        name: description.package.name.clone(),
+        version: description.package.version.clone(),
+        dependencies: description.dependencies.clone(),
+        build_steps: build_steps_from_build(&description.build),
+        url: description.source.url.clone(),
+        sha256: description.source.sha256.clone(),
+    }
+}
+
+/// Resolves a dependency's `PackageDescription` by looking for `<name>.toml` in
+/// `packages_dir` (the directory the root package description lives in). The
+/// version constraint itself isn't matched against anything yet; a single
+/// `.toml` per package is assumed to describe the version to build.
+fn fetch_description_from_dir(
+    packages_dir: &Path,
+    name: &str,
+    _version_constraint: &str,
+) -> Result<PackageDescription, Box<dyn Error>> {
+    let path = packages_dir.join(format!("{}.toml", name));
+    let toml_str = fs::read_to_string(&path).map_err(|err| {
+        format!(
+            "could not find package description for dependency '{}' at {}: {}",
+            name,
+            path.display(),
+            err
+        )
+    })?;
+    parse_package_description(&toml_str)
+}
+
+/// Extracts `tarball_path` (gzipped tar) into `dest_dir` without touching the
+/// process's global CWD, and returns the top-level directory name the archive
+/// unpacked into (instead of assuming a `{name}-version`-shaped name).
+fn extract_tarball(tarball_path: &Path, dest_dir: &Path) -> Result<String, Box<dyn Error>> {
+    let file = File::open(tarball_path)?;
+    let mut archive = Archive::new(GzDecoder::new(file));
+
+    let mut top_level_dir = None;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if top_level_dir.is_none() {
+            let path = entry.path()?;
+            let first_component = path
+                .components()
+                .next()
+                .ok_or("Tarball entry has an empty path")?;
+            top_level_dir = Some(first_component.as_os_str().to_string_lossy().to_string());
+        }
+        entry.unpack_in(dest_dir)?;
+    }
+
+    top_level_dir.ok_or_else(|| "Tarball is empty".into())
+}
+
 struct PackageManager {
     installed_packages: HashMap<String, Package>,
     store_path: PathBuf,
+    install_db: InstallDb,
 }
 
 impl PackageManager {
@@ -164,9 +253,12 @@ impl PackageManager {
             env::current_dir()?.join(store_path)
         };
 
+        let install_db = InstallDb::open(&absolute_store_path.join("simplex.db"))?;
+
         let mut pm = PackageManager {
             installed_packages: HashMap::new(),
             store_path: absolute_store_path,
+            install_db,
         };
         pm.create_directory_structure()?;
         pm.sync_installed_packages()?;
@@ -193,29 +285,7 @@ impl PackageManager {
     }
 
     fn sync_installed_packages(&mut self) -> Result<(), Box<dyn Error>> {
-        self.installed_packages.clear();
-        let installed_dir = self.store_path.join("installed");
-        for entry in fs::read_dir(installed_dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_dir() {
-                if let Some(name_version) = path.file_name() {
-                    let name_version = name_version.to_string_lossy();
-                    if let Some((name, version)) = name_version.rsplit_once('-') {
-                        let package = Package {
-                            name: name.to_string(),
-                            version: version.to_string(),
-                            dependencies: vec![], // We would need to store this info somewhere
-                            build_steps: vec![],  // Same here
-                            url: String::new(),   // And here
-                        };
-                        self.installed_packages
-                            .insert(name_version.to_string(), package);
-                    }
-                }
-            }
-        }
-
+        self.installed_packages = self.install_db.list_packages()?;
         Ok(())
     }
 
@@ -223,11 +293,25 @@ impl PackageManager {
         println!("Fetching package: {}", package.name);
         let download_dir = Path::new(&self.store_path).join("downloads");
         fs::create_dir_all(&download_dir)?;
-        env::set_current_dir(&download_dir)?;
 
-        let output = Command::new("curl").args(["-LO", &package.url]).output()?;
-        if !output.status.success() {
-            return Err(format!("Failed to download package: {}", package.name).into());
+        let source_tarball = Path::new(&package.url)
+            .file_name()
+            .ok_or("Could not determine tarball name from url")?;
+        let tarball_path = download_dir.join(source_tarball);
+
+        let response = ureq::get(&package.url).call()?;
+        let mut body = response.into_reader();
+        let mut tarball_file = File::create(&tarball_path)?;
+        io::copy(&mut body, &mut tarball_file)?;
+
+        let actual_sha256 = checksum::sha256_hex(&tarball_path)?;
+        if !actual_sha256.eq_ignore_ascii_case(&package.sha256) {
+            fs::remove_file(&tarball_path)?;
+            return Err(format!(
+                "sha256 mismatch for {}: expected {}, got {}",
+                package.name, package.sha256, actual_sha256
+            )
+            .into());
         }
 
         Ok(())
@@ -236,29 +320,19 @@ impl PackageManager {
     fn build_package(&self, package: &Package) -> Result<(), Box<dyn Error>> {
         println!("Building package: {}", package.name);
 
-        // Create and move to build directory
         let build_dir =
             Path::new(&self.store_path).join(format!("{}-{}-build", package.name, package.version));
         fs::create_dir_all(&build_dir)?;
-        env::set_current_dir(&build_dir)?;
 
-        // Extract the source
-        // TODO(Thomas): Deal with unwraps
         let source_tarball = Path::new(&package.url)
             .file_name()
-            .unwrap()
-            .to_str()
-            .unwrap();
+            .ok_or("Could not determine tarball name from url")?;
         let download_dir = Path::new(&self.store_path).join("downloads");
         let tarball_path = download_dir.join(source_tarball);
 
-        Command::new("tar")
-            .args(["xzf", tarball_path.to_str().unwrap()])
-            .status()?;
-
-        // Move into the extracted directory
-        let source_dir = build_dir.join(format!("{}-autoconf-3360000", package.name));
-        env::set_current_dir(source_dir)?;
+        // Don't assume the tarball extracts into a `{name}-{version}`-shaped name
+        // (e.g. autoconf's `-autoconf-3360000` suffix) - ask the archive itself.
+        let source_dir = build_dir.join(extract_tarball(&tarball_path, &build_dir)?);
 
         // Modify build steps to use our store path
         let install_path = Path::new(&self.store_path)
@@ -276,24 +350,39 @@ impl PackageManager {
             })
             .collect();
 
-        // Execute build steps
-        for step in &modified_build_steps {
+        let log_dir = self
+            .store_path
+            .join("builds")
+            .join(format!("{}-{}", package.name, package.version));
+        fs::create_dir_all(&log_dir)?;
+
+        // Execute build steps in the extracted source dir, without touching the
+        // process's global CWD, so concurrent builds don't clobber each other.
+        for (step_index, step) in modified_build_steps.iter().enumerate() {
             println!("Executing: {}", step);
-            let output = Command::new("sh").arg("-c").arg(step).output()?;
+            let output = Command::new("sh")
+                .arg("-c")
+                .arg(step)
+                .current_dir(&source_dir)
+                .output()?;
+
+            let log_path = log_dir.join(format!("step-{:02}.log", step_index));
+            let mut log_contents = format!("$ {}\n--- stdout ---\n", step).into_bytes();
+            log_contents.extend_from_slice(&output.stdout);
+            log_contents.extend_from_slice(b"--- stderr ---\n");
+            log_contents.extend_from_slice(&output.stderr);
+            fs::write(&log_path, &log_contents)?;
 
             if !output.status.success() {
                 return Err(format!(
-                    "Build step failed: {}\nOutput: {}",
+                    "Build step failed: {} (see {})",
                     step,
-                    String::from_utf8_lossy(&output.stderr)
+                    log_path.display()
                 )
                 .into());
             }
         }
 
-        // Return to original directory
-        env::set_current_dir(Path::new(&self.store_path))?;
-
         Ok(())
     }
 
@@ -304,10 +393,12 @@ impl PackageManager {
             .join("installed")
             .join(format!("{}-{}", package.name, package.version));
 
-        // The `make install` step should have already installed the package to our custom prefix
-        // We just need to record that it's installed
-        self.installed_packages
-            .insert(package.name.clone(), package.clone());
+        // The `make install` step should have already installed the package to our custom prefix.
+        // We record the fully resolved package (deps, build steps, url) in the install DB so it
+        // survives restarts instead of being reconstructed from the directory name alone.
+        self.install_db.insert_package(package)?;
+        let key = format!("{}-{}", package.name, package.version);
+        self.installed_packages.insert(key, package.clone());
         println!("Package installed to: {}", install_path.display());
 
         Ok(())
@@ -315,14 +406,74 @@ impl PackageManager {
 
     fn remove_package(&mut self, name: &str, version: &str) -> Result<(), Box<dyn Error>> {
         let key = format!("{}-{}", name, version);
-        if self.installed_packages.remove(&key).is_some() {
-            let install_path = self.store_path.join("installed").join(&key);
-            println!("Removing package: {} {}", name, version);
-            fs::remove_dir_all(install_path)?;
-            Ok(())
-        } else {
-            Err(format!("Package not found: {} {}", name, version).into())
+        if !self.installed_packages.contains_key(&key) {
+            return Err(format!("Package not found: {} {}", name, version).into());
+        }
+
+        let dependents = self.install_db.find_dependents(name, version)?;
+        if !dependents.is_empty() {
+            return Err(format!(
+                "cannot remove {}: still required by {}",
+                key,
+                dependents.join(", ")
+            )
+            .into());
         }
+
+        self.remove_package_unchecked(&key)
+    }
+
+    /// Removes `name-version` and then garbage-collects any of its dependencies
+    /// that were pulled in only for it and are now orphaned (no remaining
+    /// dependents), recursing through the orphan's own dependencies.
+    fn purge_package(&mut self, name: &str, version: &str) -> Result<(), Box<dyn Error>> {
+        let key = format!("{}-{}", name, version);
+        let package = self
+            .installed_packages
+            .get(&key)
+            .cloned()
+            .ok_or_else(|| format!("Package not found: {} {}", name, version))?;
+
+        let dependents = self.install_db.find_dependents(name, version)?;
+        if !dependents.is_empty() {
+            return Err(format!(
+                "cannot purge {}: still required by {}",
+                key,
+                dependents.join(", ")
+            )
+            .into());
+        }
+
+        self.remove_package_unchecked(&key)?;
+
+        // `package.dependencies` maps each dependency name to the resolved
+        // version it was actually installed at (see the "install" command),
+        // so we can check and remove that exact version instead of guessing
+        // at one among possibly several installed versions sharing the name.
+        let mut candidates: Vec<(String, String)> = package.dependencies.into_iter().collect();
+        while let Some((dep_name, dep_version)) = candidates.pop() {
+            if !self.install_db.find_dependents(&dep_name, &dep_version)?.is_empty() {
+                continue;
+            }
+
+            let orphaned_key = format!("{}-{}", dep_name, dep_version);
+            if let Some(orphaned_package) = self.installed_packages.get(&orphaned_key).cloned() {
+                println!("Removing orphaned dependency: {}", orphaned_key);
+                self.remove_package_unchecked(&orphaned_key)?;
+                candidates.extend(orphaned_package.dependencies);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn remove_package_unchecked(&mut self, key: &str) -> Result<(), Box<dyn Error>> {
+        let install_path = self.store_path.join("installed").join(key);
+        println!("Removing package: {}", key);
+        fs::remove_dir_all(install_path)?;
+        self.install_db.remove_package(key)?;
+        self.installed_packages.remove(key);
+        Ok(())
     }
 
     fn list_packages(&self) {
@@ -332,13 +483,60 @@ impl PackageManager {
         }
     }
 
+    /// Shows drift between a lockfile's resolved closure and what's actually installed.
+    fn list_packages_locked(&self, locked: &[lock::LockedPackage]) {
+        println!("Locked vs installed:");
+        for locked_package in locked {
+            let key = format!("{}-{}", locked_package.name, locked_package.version);
+            match self.installed_packages.get(&key) {
+                Some(installed) if installed.sha256.eq_ignore_ascii_case(&locked_package.sha256) => {
+                    println!("  {}: in sync", key);
+                }
+                Some(_) => println!("  {}: installed but sha256 differs from lockfile", key),
+                None => println!("  {}: locked but not installed", key),
+            }
+        }
+        for (key, package) in &self.installed_packages {
+            let is_locked = locked
+                .iter()
+                .any(|locked_package| locked_package.name == package.name && locked_package.version == package.version);
+            if !is_locked {
+                println!("  {}: installed but not locked", key);
+            }
+        }
+    }
+
+    /// Looks up `name` against each installed package's `.name`, not the
+    /// `"{name}-{version}"` store key - `installed_packages` is keyed by the
+    /// latter everywhere else, but `simplex info <package-name>` documents
+    /// (and passes) a bare name. Prints every installed version that matches,
+    /// since more than one can be installed side by side.
     fn package_info(&self, name: &str) {
-        if let Some(package) = self.installed_packages.get(name) {
+        let mut matches: Vec<&Package> = self
+            .installed_packages
+            .values()
+            .filter(|package| package.name == name)
+            .collect();
+
+        if matches.is_empty() {
+            println!("Package not found: {}", name);
+            return;
+        }
+
+        matches.sort_by(|a, b| a.version.cmp(&b.version));
+        for package in matches {
             println!("Package: {}", package.name);
             println!("Version: {}", package.version);
-            println!("Dependencies: {:?}", package.dependencies);
-        } else {
-            println!("Package not found: {}", name);
+            println!("URL: {}", package.url);
+            println!("SHA256: {}", package.sha256);
+            println!("Dependencies:");
+            if package.dependencies.is_empty() {
+                println!("  (none)");
+            } else {
+                for (dep_name, dep_version) in &package.dependencies {
+                    println!("  {} {}", dep_name, dep_version);
+                }
+            }
         }
     }
 }
@@ -349,18 +547,32 @@ enum SimplexError {
     MissingInstallPackage,
     MissingRemovePackageName,
     MissingRemovePackageVersion,
+    MissingPurgePackageName,
+    MissingPurgePackageVersion,
+    MissingLockPackage,
+    MissingLockedListPackage,
     MissingInfoPackage,
-    IllegalCommand,
+    UnknownCommand {
+        input: String,
+        suggestion: Option<String>,
+    },
 }
 
 impl fmt::Display for SimplexError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             SimplexError::MissingCommand => write!(f, "User did not specify command. Usage: simplex <command> [<args>]"),
-            SimplexError::MissingInstallPackage => write!(f, "User did not specify which package to be installed. Usage: simplex install <package-name>"),
-            SimplexError::IllegalCommand => write!(f, "User specified an illegal command. For more info about legal commands: simplex --help"),
+            SimplexError::MissingInstallPackage => write!(f, "User did not specify which package description to install. Usage: simplex install <path-to.toml>"),
+            SimplexError::UnknownCommand { ref input, ref suggestion } => match suggestion {
+                Some(suggestion) => write!(f, "unknown command `{}`; did you mean `{}`?", input, suggestion),
+                None => write!(f, "unknown command `{}`. Known commands: {}", input, cli::KNOWN_COMMANDS.join(", ")),
+            },
             SimplexError::MissingRemovePackageName => write!(f, "User did not specify which package to be removed. Usage: simplex remove <package-name> <package-version>"),
             SimplexError::MissingRemovePackageVersion => write!(f, "User did not specify which version of the package to be removed. Usage: simplex remove <package-name> <package-version>"),
+            SimplexError::MissingPurgePackageName => write!(f, "User did not specify which package to be purged. Usage: simplex purge <package-name> <package-version>"),
+            SimplexError::MissingPurgePackageVersion => write!(f, "User did not specify which version of the package to be purged. Usage: simplex purge <package-name> <package-version>"),
+            SimplexError::MissingLockPackage => write!(f, "User did not specify which package description to lock. Usage: simplex lock <path-to.toml>"),
+            SimplexError::MissingLockedListPackage => write!(f, "User did not specify which package description's lockfile to compare against. Usage: simplex list --locked <path-to.toml>"),
             SimplexError::MissingInfoPackage => write!(f, "User did not specify which package to get more information about. Usage: simplex info <package-name>"),
         }
     }
@@ -376,27 +588,93 @@ fn run() -> Result<(), Box<dyn Error>> {
         return Err(Box::new(SimplexError::MissingCommand));
     }
 
+    let aliases = cli::load_aliases(&pm.store_path.join("config.toml"))?;
+    let args = cli::expand_aliases(args, &aliases);
+
     match args[1].as_str() {
         "install" => {
             if args.len() < 3 {
                 return Err(Box::new(SimplexError::MissingInstallPackage));
             }
-            println!("Installing package {} ...", args[2]);
-            let sqlite = Package {
-                name: "sqlite".to_string(),
-                version: "3.36.0".to_string(),
-                dependencies: vec![],
-                build_steps: vec![
-                    "./configure".to_string(),
-                    "make".to_string(),
-                    "make install".to_string(),
-                ],
+            let toml_path = Path::new(&args[2]);
+            let packages_dir = toml_path.parent().unwrap_or_else(|| Path::new("."));
+            let toml_str = fs::read_to_string(toml_path)?;
+            let description = parse_package_description(&toml_str)?;
+
+            let mut build_order = resolver::resolve_build_order(&description, &|name, version| {
+                fetch_description_from_dir(packages_dir, name, version)
+            })?;
+
+            let lock_path = lock::lockfile_path(packages_dir);
+            if let Some(locked_packages) = lock::read_lockfile(&lock_path)? {
+                for package in &mut build_order {
+                    if let Some(locked) = locked_packages.iter().find(|l| l.name == package.name) {
+                        package.version = locked.version.clone();
+                        package.url = locked.url.clone();
+                        package.sha256 = locked.sha256.clone();
+                    }
+                }
+            }
 
-                url: "https://www.sqlite.org/2021/sqlite-autoconf-3360000.tar.gz".to_string(),
-            };
-            pm.fetch_package(&sqlite)?;
-            pm.build_package(&sqlite)?;
-            pm.install_package(&sqlite)?;
+            // Track the exact version each package name resolved to in this
+            // closure, so dependency edges can be recorded against the
+            // version actually installed rather than the TOML's version
+            // range - that's what lets `find_dependents` guard removal of a
+            // specific version instead of any package sharing its name.
+            let mut resolved_versions: HashMap<String, String> = pm
+                .installed_packages
+                .values()
+                .map(|installed| (installed.name.clone(), installed.version.clone()))
+                .collect();
+            for package in &build_order {
+                resolved_versions.insert(package.name.clone(), package.version.clone());
+            }
+
+            for package in &build_order {
+                let key = format!("{}-{}", package.name, package.version);
+                if pm.installed_packages.contains_key(&key) {
+                    println!("{} is already installed, skipping", key);
+                    continue;
+                }
+                println!("Installing package {} ...", package.name);
+                pm.fetch_package(package)?;
+                pm.build_package(package)?;
+
+                let mut resolved_package = package.clone();
+                resolved_package.dependencies = resolved_package
+                    .dependencies
+                    .keys()
+                    .filter_map(|dep_name| {
+                        resolved_versions
+                            .get(dep_name)
+                            .map(|dep_version| (dep_name.clone(), dep_version.clone()))
+                    })
+                    .collect();
+                pm.install_package(&resolved_package)?;
+            }
+
+            let locked_closure: Vec<lock::LockedPackage> =
+                build_order.iter().map(lock::LockedPackage::from).collect();
+            lock::write_lockfile(&lock_path, &locked_closure)?;
+        }
+        "lock" => {
+            if args.len() < 3 {
+                return Err(Box::new(SimplexError::MissingLockPackage));
+            }
+            let toml_path = Path::new(&args[2]);
+            let packages_dir = toml_path.parent().unwrap_or_else(|| Path::new("."));
+            let toml_str = fs::read_to_string(toml_path)?;
+            let description = parse_package_description(&toml_str)?;
+
+            let build_order = resolver::resolve_build_order(&description, &|name, version| {
+                fetch_description_from_dir(packages_dir, name, version)
+            })?;
+            let locked_closure: Vec<lock::LockedPackage> =
+                build_order.iter().map(lock::LockedPackage::from).collect();
+
+            let lock_path = lock::lockfile_path(packages_dir);
+            lock::write_lockfile(&lock_path, &locked_closure)?;
+            println!("Wrote lockfile: {}", lock_path.display());
         }
         "remove" => {
             if args.len() < 3 {
@@ -405,10 +683,31 @@ fn run() -> Result<(), Box<dyn Error>> {
             if args.len() < 4 {
                 return Err(Box::new(SimplexError::MissingRemovePackageVersion));
             }
-            pm.remove_package(args[3].as_str(), args[4].as_ref())?;
+            pm.remove_package(args[2].as_str(), args[3].as_ref())?;
+        }
+        "purge" => {
+            if args.len() < 3 {
+                return Err(Box::new(SimplexError::MissingPurgePackageName));
+            }
+            if args.len() < 4 {
+                return Err(Box::new(SimplexError::MissingPurgePackageVersion));
+            }
+            pm.purge_package(args[2].as_str(), args[3].as_ref())?;
         }
         "list" => {
-            pm.list_packages();
+            if args.len() > 2 && args[2] == "--locked" {
+                if args.len() < 4 {
+                    return Err(Box::new(SimplexError::MissingLockedListPackage));
+                }
+                let toml_path = Path::new(&args[3]);
+                let packages_dir = toml_path.parent().unwrap_or_else(|| Path::new("."));
+                let lock_path = lock::lockfile_path(packages_dir);
+                let locked_packages = lock::read_lockfile(&lock_path)?
+                    .ok_or_else(|| format!("No lockfile found at {}", lock_path.display()))?;
+                pm.list_packages_locked(&locked_packages);
+            } else {
+                pm.list_packages();
+            }
         }
         "info" => {
             if args.len() < 3 {
@@ -416,7 +715,12 @@ fn run() -> Result<(), Box<dyn Error>> {
             }
             pm.package_info(&args[2]);
         }
-        _ => return Err(Box::new(SimplexError::IllegalCommand)),
+        unknown => {
+            return Err(Box::new(SimplexError::UnknownCommand {
+                input: unknown.to_string(),
+                suggestion: cli::suggest_command(unknown, cli::KNOWN_COMMANDS),
+            }))
+        }
     }
 
     Ok(())
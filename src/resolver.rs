@@ -0,0 +1,185 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+
+use crate::{package_from_description, Package, PackageDescription};
+
+/// Looks up the `PackageDescription` for a dependency given its name and the
+/// version constraint it was requested under (e.g. `"^6.0"`).
+pub type DescriptionFetcher<'a> =
+    dyn Fn(&str, &str) -> Result<PackageDescription, Box<dyn Error>> + 'a;
+
+#[derive(Debug)]
+pub enum ResolverError {
+    Cycle(String),
+    FetchFailed { dependency: String, source: String },
+}
+
+impl fmt::Display for ResolverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResolverError::Cycle(path) => write!(f, "dependency cycle: {}", path),
+            ResolverError::FetchFailed { dependency, source } => {
+                write!(f, "failed to resolve dependency '{}': {}", dependency, source)
+            }
+        }
+    }
+}
+
+impl Error for ResolverError {}
+
+/// Walks the dependency graph rooted at `root`, fetching each dependency's own
+/// description via `fetch_description`, and returns the packages in
+/// dependencies-first (topological) build order.
+///
+/// Implemented as a DFS that tracks `visited` (fully resolved nodes) and
+/// `stack` (nodes on the current recursion path); re-entering a node still on
+/// `stack` means the graph has a cycle.
+pub fn resolve_build_order(
+    root: &PackageDescription,
+    fetch_description: &DescriptionFetcher,
+) -> Result<Vec<Package>, ResolverError> {
+    let mut order = Vec::new();
+    let mut visited = HashSet::new();
+    let mut stack = Vec::new();
+    visit(root, fetch_description, &mut visited, &mut stack, &mut order)?;
+    Ok(order)
+}
+
+fn visit(
+    description: &PackageDescription,
+    fetch_description: &DescriptionFetcher,
+    visited: &mut HashSet<String>,
+    stack: &mut Vec<String>,
+    order: &mut Vec<Package>,
+) -> Result<(), ResolverError> {
+    let id = format!("{}-{}", description.package.name, description.package.version);
+
+    if stack.contains(&id) {
+        let mut path = stack.clone();
+        path.push(id);
+        return Err(ResolverError::Cycle(path.join(" -> ")));
+    }
+    if visited.contains(&id) {
+        return Ok(());
+    }
+
+    stack.push(id.clone());
+    for (dep_name, dep_version) in &description.dependencies {
+        let dep_description =
+            fetch_description(dep_name, dep_version).map_err(|err| ResolverError::FetchFailed {
+                dependency: dep_name.clone(),
+                source: err.to_string(),
+            })?;
+        visit(&dep_description, fetch_description, visited, stack, order)?;
+    }
+    stack.pop();
+
+    visited.insert(id);
+    order.push(package_from_description(description));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{Build, NewPackage, Source};
+
+    fn description(name: &str, version: &str, dependencies: &[(&str, &str)]) -> PackageDescription {
+        PackageDescription {
+            package: NewPackage {
+                name: name.to_string(),
+                version: version.to_string(),
+            },
+            source: Source {
+                url: format!("https://example.com/{}-{}.tar.gz", name, version),
+                sha256: "0".repeat(64),
+            },
+            build: Build {
+                system: "make".to_string(),
+                arguments: vec![],
+            },
+            dependencies: dependencies
+                .iter()
+                .map(|(name, version)| (name.to_string(), version.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_build_order_linear_chain() {
+        let a = description("a", "1.0", &[("b", "^1.0")]);
+        let b = description("b", "1.0", &[("c", "^1.0")]);
+        let c = description("c", "1.0", &[]);
+
+        let packages: HashMap<String, PackageDescription> = [("b", b), ("c", c)]
+            .into_iter()
+            .map(|(name, description)| (name.to_string(), description))
+            .collect();
+
+        let order = resolve_build_order(&a, &|name, _version| {
+            packages
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("unknown dependency: {}", name).into())
+        })
+        .unwrap();
+
+        let names: Vec<&str> = order.iter().map(|package| package.name.as_str()).collect();
+        assert_eq!(names, vec!["c", "b", "a"]);
+    }
+
+    #[test]
+    fn test_resolve_build_order_diamond_dedup() {
+        // a depends on b and c, both of which depend on d. d must only appear once.
+        let a = description("a", "1.0", &[("b", "^1.0"), ("c", "^1.0")]);
+        let b = description("b", "1.0", &[("d", "^1.0")]);
+        let c = description("c", "1.0", &[("d", "^1.0")]);
+        let d = description("d", "1.0", &[]);
+
+        let packages: HashMap<String, PackageDescription> = [("b", b), ("c", c), ("d", d)]
+            .into_iter()
+            .map(|(name, description)| (name.to_string(), description))
+            .collect();
+
+        let order = resolve_build_order(&a, &|name, _version| {
+            packages
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("unknown dependency: {}", name).into())
+        })
+        .unwrap();
+
+        let names: Vec<&str> = order.iter().map(|package| package.name.as_str()).collect();
+        assert_eq!(names.iter().filter(|&&name| name == "d").count(), 1);
+        assert_eq!(names.last(), Some(&"a"));
+        assert!(names.iter().position(|&name| name == "d").unwrap() < names.iter().position(|&name| name == "b").unwrap());
+        assert!(names.iter().position(|&name| name == "d").unwrap() < names.iter().position(|&name| name == "c").unwrap());
+    }
+
+    #[test]
+    fn test_resolve_build_order_cycle() {
+        let a = description("a", "1.0", &[("b", "^1.0")]);
+        let b = description("b", "1.0", &[("a", "^1.0")]);
+
+        let packages: HashMap<String, PackageDescription> = [("a", a.clone()), ("b", b)]
+            .into_iter()
+            .map(|(name, description)| (name.to_string(), description))
+            .collect();
+
+        let err = resolve_build_order(&a, &|name, _version| {
+            packages
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("unknown dependency: {}", name).into())
+        })
+        .unwrap_err();
+
+        match err {
+            ResolverError::Cycle(path) => assert_eq!(path, "a-1.0 -> b-1.0 -> a-1.0"),
+            other => panic!("expected ResolverError::Cycle, got {:?}", other),
+        }
+    }
+}